@@ -0,0 +1,197 @@
+use anyhow::{Context, Result};
+use hashbrown::HashSet;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+
+use crate::progress::ProgressTracker;
+
+/// Tunable chunk-size bounds for content-defined chunking, in bytes.
+#[derive(Copy, Clone, Debug)]
+pub struct CdcParams {
+    pub min_size: usize,
+    pub avg_size: usize,
+    pub max_size: usize,
+}
+
+/// Precompute the 256-entry Gear table used by the rolling fingerprint.
+///
+/// The values need only be fixed and well-distributed, so they are derived
+/// deterministically from a SplitMix64 sequence rather than stored as a
+/// literal table.
+fn gear_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut state = 0x2545_F491_4F6C_DD1Du64;
+    for slot in table.iter_mut() {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        *slot = z ^ (z >> 31);
+    }
+    table
+}
+
+/// A low-order mask with `bits` one-bits, giving a cut probability of
+/// `1 / 2^bits` for a uniform fingerprint.
+fn mask(bits: u32) -> u64 {
+    (1u64 << bits) - 1
+}
+
+/// FastCDC chunker that reads a stream and yields content-defined chunks.
+///
+/// Uses gear hashing with normalized chunking: the first `min_size` bytes are
+/// skipped, then a stricter `mask_large` (more one-bits, lower cut
+/// probability) is applied until the average size is reached, after which a
+/// looser `mask_small` takes over; a cut is forced at `max_size`. This tightens
+/// the size distribution relative to a plain Rabin cut.
+pub struct StreamCdc<R> {
+    reader: R,
+    params: CdcParams,
+    gear: [u64; 256],
+    mask_large: u64,
+    mask_small: u64,
+    buf: Vec<u8>,
+    eof: bool,
+}
+
+impl<R: Read> StreamCdc<R> {
+    pub fn new(reader: R, params: CdcParams) -> Self {
+        let bits = (params.avg_size as f64).log2().round() as u32;
+        Self {
+            reader,
+            params,
+            gear: gear_table(),
+            mask_large: mask(bits + 2),
+            mask_small: mask(bits.saturating_sub(2)),
+            buf: Vec::new(),
+            eof: false,
+        }
+    }
+
+    /// Fill the internal buffer until it holds at least `max_size` bytes or the
+    /// underlying reader is exhausted.
+    fn fill(&mut self) -> Result<()> {
+        let mut tmp = [0u8; 64 * 1024];
+        while !self.eof && self.buf.len() < self.params.max_size {
+            let n = self.reader.read(&mut tmp).context("Failed to read input stream")?;
+            if n == 0 {
+                self.eof = true;
+                break;
+            }
+            self.buf.extend_from_slice(&tmp[..n]);
+        }
+        Ok(())
+    }
+
+    /// Find the cut point within `data`, i.e. the length of the first chunk.
+    fn boundary(&self, data: &[u8]) -> usize {
+        let len = data.len();
+        if len <= self.params.min_size {
+            return len;
+        }
+        let scan_end = len.min(self.params.max_size);
+        let center = scan_end.min(self.params.avg_size);
+
+        let mut fp = 0u64;
+        let mut i = self.params.min_size;
+
+        // Below the average size: harder to cut (stricter mask).
+        while i < center {
+            fp = (fp << 1).wrapping_add(self.gear[data[i] as usize]);
+            if fp & self.mask_large == 0 {
+                return i + 1;
+            }
+            i += 1;
+        }
+        // Past the average size: easier to cut (looser mask).
+        while i < scan_end {
+            fp = (fp << 1).wrapping_add(self.gear[data[i] as usize]);
+            if fp & self.mask_small == 0 {
+                return i + 1;
+            }
+            i += 1;
+        }
+        scan_end
+    }
+
+    /// Return the next chunk, or `None` once the stream is fully consumed.
+    pub fn next_chunk(&mut self) -> Result<Option<Vec<u8>>> {
+        self.fill()?;
+        if self.buf.is_empty() {
+            return Ok(None);
+        }
+        let cut = self.boundary(&self.buf);
+        let chunk: Vec<u8> = self.buf.drain(..cut).collect();
+        Ok(Some(chunk))
+    }
+}
+
+/// Deduplicate a file at the block level using FastCDC.
+///
+/// Each content-defined chunk is hashed with BLAKE3; only the first occurrence
+/// of a given digest is written to `output_path`, while `<output_path>.manifest`
+/// records the digest and length of every chunk in order so the original stream
+/// can be reconstructed.
+pub fn deduplicate_cdc(
+    input_path: &std::path::Path,
+    output_path: &std::path::Path,
+    params: CdcParams,
+    progress: &ProgressTracker,
+) -> Result<()> {
+    let input = File::open(input_path)
+        .with_context(|| format!("Failed to open file: {}", input_path.display()))?;
+    let reader = BufReader::with_capacity(256 * 1024, input);
+    let mut chunker = StreamCdc::new(reader, params);
+
+    let output = File::create(output_path)
+        .with_context(|| format!("Failed to create output file: {}", output_path.display()))?;
+    let mut writer = BufWriter::with_capacity(1024 * 1024, output);
+
+    let manifest_path = manifest_path_for(output_path);
+    let manifest = File::create(&manifest_path)
+        .with_context(|| format!("Failed to create manifest file: {}", manifest_path.display()))?;
+    let mut manifest_writer = BufWriter::new(manifest);
+
+    let mut seen = HashSet::<[u8; 32]>::new();
+    let mut total_chunks = 0u64;
+    let mut duplicate_chunks = 0u64;
+
+    while let Some(chunk) = chunker.next_chunk()? {
+        let digest = blake3::hash(&chunk);
+        let bytes = *digest.as_bytes();
+        total_chunks += 1;
+        progress.increment_lines(1);
+
+        writeln!(manifest_writer, "{} {}", digest.to_hex(), chunk.len())
+            .context("Failed to write manifest entry")?;
+
+        if seen.insert(bytes) {
+            writer.write_all(&chunk).context("Failed to write chunk to output")?;
+        } else {
+            duplicate_chunks += 1;
+            progress.increment_duplicates(1);
+        }
+    }
+
+    writer.flush().context("Failed to flush output")?;
+    manifest_writer.flush().context("Failed to flush manifest")?;
+
+    let metrics = progress.finish();
+    eprintln!("{}", metrics);
+    eprintln!(
+        "  CDC chunks: {} total, {} unique, {} duplicate",
+        total_chunks,
+        total_chunks - duplicate_chunks,
+        duplicate_chunks
+    );
+    eprintln!("  Manifest: {}", manifest_path.display());
+
+    Ok(())
+}
+
+/// Sibling manifest path for a given output path (`<output>.manifest`).
+fn manifest_path_for(output_path: &std::path::Path) -> std::path::PathBuf {
+    let mut name = output_path.as_os_str().to_owned();
+    name.push(".manifest");
+    std::path::PathBuf::from(name)
+}
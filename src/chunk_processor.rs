@@ -1,5 +1,5 @@
-use hashbrown::HashSet;
-use std::io::{BufRead, BufReader, Read, Seek, Write};
+use hashbrown::{HashMap, HashSet};
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, Write};
 use std::fs::File;
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
@@ -10,6 +10,200 @@ pub struct Chunk {
     pub end_offset: u64,
 }
 
+/// Which occurrence of a repeated line to retain in the output.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum KeepMode {
+    /// Keep the first occurrence (the default, classic `uniq` behaviour).
+    First,
+    /// Keep the last occurrence — useful for newest-wins log/record dedup.
+    Last,
+}
+
+/// Hash algorithm used to derive the 128-bit dedup key from each line.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum HashAlgorithm {
+    /// Standard-library SipHash, run twice to fill 128 bits.
+    Siphash,
+    /// xxHash3, a fast non-cryptographic 128-bit hash.
+    Xxh3,
+    /// BLAKE3 cryptographic hash, truncated to 128 bits.
+    Blake3,
+}
+
+/// Produces a 128-bit, case-insensitive key for a line.
+///
+/// Widening the key from the original 64 bits makes an accidental collision
+/// (and the silent data loss it caused) astronomically unlikely; `--exact`
+/// closes the gap entirely by falling back to a byte comparison.
+pub trait LineHasher: Send + Sync {
+    fn hash128(&self, canonical: &str) -> u128;
+}
+
+struct SipHasher;
+struct Xxh3Hasher;
+struct Blake3Hasher;
+
+impl LineHasher for SipHasher {
+    fn hash128(&self, canonical: &str) -> u128 {
+        // SipHash yields 64 bits; run a second, differently-seeded pass to
+        // fill the upper half of the key.
+        let mut lo = DefaultHasher::new();
+        canonical.hash(&mut lo);
+        let mut hi = DefaultHasher::new();
+        0x9E3779B97F4A7C15u64.hash(&mut hi);
+        canonical.hash(&mut hi);
+        ((hi.finish() as u128) << 64) | lo.finish() as u128
+    }
+}
+
+impl LineHasher for Xxh3Hasher {
+    fn hash128(&self, canonical: &str) -> u128 {
+        xxhash_rust::xxh3::xxh3_128(canonical.as_bytes())
+    }
+}
+
+impl LineHasher for Blake3Hasher {
+    fn hash128(&self, canonical: &str) -> u128 {
+        let digest = blake3::hash(canonical.as_bytes());
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(&digest.as_bytes()[..16]);
+        u128::from_le_bytes(bytes)
+    }
+}
+
+impl HashAlgorithm {
+    /// Build the boxed hasher for this algorithm.
+    pub fn hasher(self) -> Box<dyn LineHasher> {
+        match self {
+            HashAlgorithm::Siphash => Box::new(SipHasher),
+            HashAlgorithm::Xxh3 => Box::new(Xxh3Hasher),
+            HashAlgorithm::Blake3 => Box::new(Blake3Hasher),
+        }
+    }
+}
+
+/// Set of already-seen line keys, with an optional exact-match fallback.
+///
+/// In the default mode a 128-bit key alone decides uniqueness. In `--exact`
+/// mode the canonical (lowercased) line is retained per key so a key hit is
+/// confirmed with a byte comparison; a genuine 128-bit collision between two
+/// distinct lines keeps both rather than silently dropping one.
+pub enum SeenSet {
+    Fast(HashSet<u128>),
+    Exact(HashMap<u128, Vec<String>>),
+}
+
+impl SeenSet {
+    pub fn new(exact: bool) -> Self {
+        if exact {
+            SeenSet::Exact(HashMap::new())
+        } else {
+            SeenSet::Fast(HashSet::new())
+        }
+    }
+
+    /// Like [`SeenSet::new`] but pre-allocates room for `capacity` entries,
+    /// avoiding repeated rehashing when the cardinality is known up front.
+    pub fn with_capacity(exact: bool, capacity: usize) -> Self {
+        if exact {
+            SeenSet::Exact(HashMap::with_capacity(capacity))
+        } else {
+            SeenSet::Fast(HashSet::with_capacity(capacity))
+        }
+    }
+
+    /// Record `(key, canonical)` and return `true` if it is newly seen and
+    /// should therefore be emitted.
+    pub fn insert(&mut self, key: u128, canonical: &str) -> bool {
+        match self {
+            SeenSet::Fast(set) => set.insert(key),
+            SeenSet::Exact(map) => {
+                // Keep every distinct canonical line that shares a 128-bit key,
+                // so a genuine collision never drops a truly-unique line.
+                let bucket = map.entry(key).or_default();
+                if bucket.iter().any(|stored| stored == canonical) {
+                    false
+                } else {
+                    bucket.push(canonical.to_string());
+                    true
+                }
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            SeenSet::Fast(set) => set.len(),
+            SeenSet::Exact(map) => map.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self {
+            SeenSet::Fast(set) => set.is_empty(),
+            SeenSet::Exact(map) => map.is_empty(),
+        }
+    }
+}
+
+/// A temp file holding one chunk's locally-unique line bytes.
+///
+/// Workers spill their surviving lines to disk rather than returning them in
+/// RAM, so the merge holds only each chunk's `(hash, len)` entries and the
+/// global key set in memory, not the line text itself (in `--exact` mode the
+/// key set also stores each canonical key). The file is removed on drop, so a
+/// crashed or aborted run leaves no stray spill behind once `ChunkResult`s go
+/// out of scope.
+pub struct SpillFile {
+    path: std::path::PathBuf,
+}
+
+impl SpillFile {
+    /// Create (truncating) the spill file and hand back a buffered writer.
+    fn create(path: std::path::PathBuf) -> Result<(Self, BufWriter<File>)> {
+        let file = File::create(&path)
+            .with_context(|| format!("Failed to create spill file: {}", path.display()))?;
+        let writer = BufWriter::with_capacity(256 * 1024, file);
+        Ok((SpillFile { path }, writer))
+    }
+
+    /// Open the spill file for the merge pass to stream back.
+    pub fn open(&self) -> Result<BufReader<File>> {
+        let file = File::open(&self.path)
+            .with_context(|| format!("Failed to open spill file: {}", self.path.display()))?;
+        Ok(BufReader::with_capacity(256 * 1024, file))
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Sibling spill path for chunk `idx` of a given output path.
+pub fn spill_path_for(output_path: &std::path::Path, idx: usize) -> std::path::PathBuf {
+    let mut name = output_path.as_os_str().to_owned();
+    name.push(format!(".rsort-spill.{}", idx));
+    std::path::PathBuf::from(name)
+}
+
+/// Unique lines observed within a single chunk, in first-occurrence order.
+///
+/// Each worker dedupes locally so the sequential merge pass only has to
+/// reconcile across chunks. `lines` holds `(hash, byte_len)` for the lines that
+/// were unique *within this chunk*, in order; the line bytes themselves live in
+/// `spill` (absent when the chunk produced no unique lines). `duplicates`
+/// counts the lines that were dropped locally as within-chunk repeats.
+pub struct ChunkResult {
+    pub lines: Vec<(u128, u32)>,
+    pub spill: Option<SpillFile>,
+    pub duplicates: u64,
+    /// Lines that failed UTF-8 decoding, kept verbatim with their byte offset
+    /// when quarantine collection is enabled.
+    pub quarantined: Vec<(u64, Vec<u8>)>,
+}
+
 /// Get the size of a file in bytes
 pub fn get_file_size(file_path: &std::path::Path) -> Result<u64> {
     let metadata = std::fs::metadata(file_path)
@@ -17,20 +211,113 @@ pub fn get_file_size(file_path: &std::path::Path) -> Result<u64> {
     Ok(metadata.len())
 }
 
-/// Compute a u64 hash of a string (case-insensitive)
-fn hash_string(s: &str) -> u64 {
-    let mut hasher = DefaultHasher::new();
-    s.to_lowercase().hash(&mut hasher);
-    hasher.finish()
+/// Canonical form of a line for comparison purposes (case-insensitive).
+pub fn canonicalize(line: &str) -> String {
+    line.to_lowercase()
+}
+
+/// What to do when the requested key field or range is absent from a line.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum MissingField {
+    /// Treat the missing key as an empty string.
+    Empty,
+    /// Abort with an error.
+    Error,
+}
+
+#[derive(Clone, Debug)]
+enum KeyMode {
+    WholeLine,
+    Field { index: usize, delimiter: char },
+    Range { start: usize, end: usize },
+}
+
+/// Selects the portion of a line used as the dedup key.
+///
+/// By default the whole line is the key (preserving the original behaviour);
+/// `--key-field`/`--key-delimiter` pick a delimited column and `--key-range`
+/// picks a 1-based inclusive byte range, analogous to `sort -k` and `cut`. The
+/// full original line is always what gets written to output.
+#[derive(Clone, Debug)]
+pub struct KeyExtractor {
+    mode: KeyMode,
+    missing: MissingField,
+}
+
+impl KeyExtractor {
+    /// The whole line is the key (default).
+    pub fn whole_line() -> Self {
+        KeyExtractor { mode: KeyMode::WholeLine, missing: MissingField::Error }
+    }
+
+    /// Use the `index`-th (1-based) `delimiter`-separated field.
+    pub fn field(index: usize, delimiter: char, missing: MissingField) -> Self {
+        KeyExtractor { mode: KeyMode::Field { index, delimiter }, missing }
+    }
+
+    /// Use the 1-based inclusive byte range `start..=end`.
+    pub fn range(start: usize, end: usize, missing: MissingField) -> Self {
+        KeyExtractor { mode: KeyMode::Range { start, end }, missing }
+    }
+
+    /// Extract the key from `line`, honouring the missing-field policy.
+    pub fn extract<'a>(&self, line: &'a str) -> Result<std::borrow::Cow<'a, str>> {
+        use std::borrow::Cow;
+        match &self.mode {
+            KeyMode::WholeLine => Ok(Cow::Borrowed(line)),
+            KeyMode::Field { index, delimiter } => {
+                let trimmed = line.trim_end_matches(['\r', '\n']);
+                match trimmed.split(*delimiter).nth(index - 1) {
+                    Some(field) => Ok(Cow::Borrowed(field)),
+                    None => self.missing_key(trimmed),
+                }
+            }
+            KeyMode::Range { start, end } => {
+                let trimmed = line.trim_end_matches(['\r', '\n']);
+                let bytes = trimmed.as_bytes();
+                let s = start - 1;
+                if s >= bytes.len() {
+                    return self.missing_key(trimmed);
+                }
+                let e = (*end).min(bytes.len());
+                let slice = &bytes[s..e];
+                Ok(match std::str::from_utf8(slice) {
+                    Ok(v) => Cow::Borrowed(v),
+                    Err(_) => Cow::Owned(String::from_utf8_lossy(slice).into_owned()),
+                })
+            }
+        }
+    }
+
+    fn missing_key<'a>(&self, line: &str) -> Result<std::borrow::Cow<'a, str>> {
+        match self.missing {
+            MissingField::Empty => Ok(std::borrow::Cow::Borrowed("")),
+            MissingField::Error => Err(anyhow::anyhow!(
+                "Requested key field/range not present in line: {:?}",
+                line
+            )),
+        }
+    }
 }
 
-pub fn process_chunk_stream<W: Write>(
+/// Read a single chunk's byte range and return its locally-unique lines.
+///
+/// Workers run concurrently, so this function never touches shared state or
+/// the output writer: it builds a *local* `SeenSet` of 128-bit keys to drop
+/// within-chunk repeats, writes the surviving line bytes in order to the spill
+/// file at `spill_path`, and returns their `(hash, len)` entries for the merge
+/// pass to reconcile globally.
+#[allow(clippy::too_many_arguments)]
+pub fn process_chunk_local(
     file_path: &std::path::Path,
     chunk: &Chunk,
-    writer: &mut W,
-    global_seen: &mut HashSet<u64>,
+    hasher: &dyn LineHasher,
+    exact: bool,
+    collect_quarantine: bool,
+    key: &KeyExtractor,
+    spill_path: &std::path::Path,
     progress: &crate::progress::ProgressTracker,
-) -> Result<()> {
+) -> Result<ChunkResult> {
     // Validate chunk boundaries against file size
     let file_size = get_file_size(file_path)
         .with_context(|| format!("Failed to get file size: {}", file_path.display()))?;
@@ -68,14 +355,20 @@ pub fn process_chunk_stream<W: Write>(
         ))?;
     
     let mut current_offset = chunk.start_offset;
-    
+    let mut local_seen = SeenSet::new(exact);
+    let mut lines: Vec<(u128, u32)> = Vec::new();
+    let mut spill: Option<(SpillFile, BufWriter<File>)> = None;
+    let mut duplicates = 0u64;
+    let mut quarantined = Vec::new();
+    let mut buf = Vec::new();
+
     loop {
         if current_offset >= chunk.end_offset {
             break;
         }
-        
-        let mut line = String::new();
-        let bytes_read = reader.read_line(&mut line)
+
+        buf.clear();
+        let bytes_read = reader.read_until(b'\n', &mut buf)
             .with_context(|| format!(
                 "Failed to read line at offset {} (chunk: {}-{}, file size: {}, file: {})",
                 current_offset,
@@ -84,40 +377,66 @@ pub fn process_chunk_stream<W: Write>(
                 file_size,
                 file_path.display()
             ))?;
-        
+
         if bytes_read == 0 {
             break;
         }
-        
+
+        let line_start = current_offset;
         current_offset += bytes_read as u64;
-        
-        // Skip empty lines or handle encoding issues
-        if line.is_empty() && bytes_read == 1 {
-            // Just a newline, skip
+
+        // Skip empty lines (just a newline).
+        if buf == b"\n" {
             continue;
         }
-        
+
+        // Decode as UTF-8; invalid lines are quarantined (or dropped) rather
+        // than aborting the chunk.
+        let line = match std::str::from_utf8(&buf) {
+            Ok(line) => line.to_string(),
+            Err(_) => {
+                if collect_quarantine {
+                    quarantined.push((line_start, buf.clone()));
+                }
+                continue;
+            }
+        };
+
         progress.increment_lines(1);
-        
-        // Hash the lowercase string for case-insensitive comparison
-        let key_lower = line.to_lowercase();
-        let hash = hash_string(&key_lower);
-        
-        // Check against global HashSet and write immediately if unique
-        if global_seen.insert(hash) {
-            writer.write_all(line.as_bytes())
-                .context("Failed to write line to output")?;
+
+        // Hash the canonical (lowercased) form of the selected key for
+        // case-insensitive comparison.
+        let canonical = canonicalize(&key.extract(&line)?);
+        let hash = hasher.hash128(&canonical);
+
+        // Drop within-chunk repeats now; cross-chunk dedup happens in the merge.
+        if local_seen.insert(hash, &canonical) {
+            let bytes = line.as_bytes();
+            if spill.is_none() {
+                spill = Some(SpillFile::create(spill_path.to_path_buf())?);
+            }
+            let (_, writer) = spill.as_mut().expect("spill initialized above");
+            writer.write_all(bytes).context("Failed to write line to spill file")?;
+            lines.push((hash, bytes.len() as u32));
         } else {
-            progress.increment_duplicates(1);
-        }
-        
-        // Periodic flush to reduce bottlenecks
-        if global_seen.len() % 100_000 == 0 {
-            writer.flush().context("Failed to flush output buffer")?;
+            duplicates += 1;
         }
     }
-    
-    Ok(())
+
+    let spill = match spill {
+        Some((file, mut writer)) => {
+            writer.flush().context("Failed to flush spill file")?;
+            Some(file)
+        }
+        None => None,
+    };
+
+    Ok(ChunkResult {
+        lines,
+        spill,
+        duplicates,
+        quarantined,
+    })
 }
 
 pub fn find_chunk_boundaries(
@@ -133,62 +452,21 @@ pub fn find_chunk_boundaries(
     let mut chunk_start = 0u64;
     let mut chunk_size = 0usize;
     
+    // Read raw bytes up to each newline: boundaries must track true file
+    // positions regardless of UTF-8 validity, so the quarantine offsets and the
+    // byte ranges workers read stay correct on dirty input. Decoding (and
+    // quarantine) happens later, per line, in `process_chunk_local`.
+    let mut buf = Vec::new();
     loop {
-        let mut line = String::new();
-        match reader.read_line(&mut line) {
-            Ok(0) => break, // EOF
-            Ok(bytes_read) => {
-                chunk_size += bytes_read;
-                current_offset += bytes_read as u64;
-            }
-            Err(e) => {
-                // Handle encoding errors - log warning and try to recover
-                if e.kind() == std::io::ErrorKind::InvalidData {
-                    eprintln!("Warning: Invalid UTF-8 sequence at offset {}. Attempting to recover.", current_offset);
-                    // Read raw bytes until we find a newline or EOF
-                    let mut buffer = vec![0u8; 1];
-                    let mut found_newline = false;
-                    let mut bytes_skipped = 0;
-                    
-                    // Skip bytes until we find a newline (0x0A) or EOF
-                    while let Ok(1) = reader.read(&mut buffer) {
-                        bytes_skipped += 1;
-                        current_offset += 1;
-                        chunk_size += 1;
-                        if buffer[0] == b'\n' {
-                            found_newline = true;
-                            break;
-                        }
-                        if bytes_skipped > 10000 {
-                            // Too many bytes skipped, likely corrupted file
-                            eprintln!("Error: Too many invalid bytes encountered. File may be corrupted.");
-                            break;
-                        }
-                    }
-                    
-                    if found_newline {
-                        // Check if we need to finalize chunk
-                        if chunk_size >= chunk_size_bytes {
-                            chunks.push(Chunk {
-                                start_offset: chunk_start,
-                                end_offset: current_offset,
-                            });
-                            chunk_start = current_offset;
-                            chunk_size = 0;
-                        }
-                        // Continue to next iteration of loop
-                        continue;
-                    } else {
-                        // EOF or unrecoverable - exit loop
-                        break;
-                    }
-                } else {
-                    // Other I/O error
-                    return Err(e).with_context(|| format!("I/O error at offset {}", current_offset));
-                }
-            }
+        buf.clear();
+        let bytes_read = reader.read_until(b'\n', &mut buf)
+            .with_context(|| format!("I/O error at offset {}", current_offset))?;
+        if bytes_read == 0 {
+            break; // EOF
         }
-        
+        chunk_size += bytes_read;
+        current_offset += bytes_read as u64;
+
         // If chunk size exceeded, finalize current chunk and start new one
         if chunk_size >= chunk_size_bytes {
             chunks.push(Chunk {
@@ -219,3 +497,248 @@ pub fn find_chunk_boundaries(
     Ok(chunks)
 }
 
+/// Reads a file in fixed-size blocks from the end toward the start.
+///
+/// Modeled on the `ReverseChunks` iterator in uutils `tail`: it seeks to EOF
+/// and walks backward one `REVERSE_BLOCK_SIZE` window at a time, yielding each
+/// block's bytes together with its starting offset so callers can reassemble
+/// lines across block boundaries themselves.
+pub struct ReverseChunks {
+    file: File,
+    pos: u64,
+}
+
+const REVERSE_BLOCK_SIZE: u64 = 64 * 1024;
+
+impl ReverseChunks {
+    pub fn new(file: File) -> Result<Self> {
+        let pos = file.metadata()
+            .context("Failed to read file metadata for reverse scan")?
+            .len();
+        Ok(Self { file, pos })
+    }
+}
+
+impl Iterator for ReverseChunks {
+    type Item = Result<(u64, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos == 0 {
+            return None;
+        }
+        let this_block = std::cmp::min(self.pos, REVERSE_BLOCK_SIZE);
+        let start = self.pos - this_block;
+        let mut buf = vec![0u8; this_block as usize];
+        if let Err(e) = self.file.seek(std::io::SeekFrom::Start(start)) {
+            return Some(Err(e.into()));
+        }
+        if let Err(e) = self.file.read_exact(&mut buf) {
+            return Some(Err(e.into()));
+        }
+        self.pos = start;
+        Some(Ok((start, buf)))
+    }
+}
+
+/// Emit one line and mark it as a keeper if it is the first time (reading
+/// backward) we have seen its key — i.e. the *last* occurrence in the file.
+fn mark_keeper(
+    line_start: u64,
+    bytes: &[u8],
+    hasher: &dyn LineHasher,
+    key: &KeyExtractor,
+    seen: &mut SeenSet,
+    keepers: &mut HashSet<u64>,
+    progress: &crate::progress::ProgressTracker,
+) -> Result<()> {
+    // Mirror the forward pass: a bare newline is skipped entirely.
+    if bytes == b"\n" || bytes.is_empty() {
+        return Ok(());
+    }
+    // Lines that are not valid UTF-8 are skipped here just as the forward
+    // reader's recovery path skips them, so they never become keepers.
+    let Ok(line) = std::str::from_utf8(bytes) else {
+        return Ok(());
+    };
+    progress.increment_lines(1);
+    let canonical = canonicalize(&key.extract(line)?);
+    let hash = hasher.hash128(&canonical);
+    if seen.insert(hash, &canonical) {
+        keepers.insert(line_start);
+    }
+    Ok(())
+}
+
+/// Scan the file from the end and return the start offset of the line to keep
+/// for each distinct key — the last occurrence of that key in the file.
+///
+/// The forward pass then writes exactly the lines whose start offset is in the
+/// returned set, preserving original order while keeping the newest record.
+pub fn find_last_occurrence_offsets(
+    file_path: &std::path::Path,
+    hasher: &dyn LineHasher,
+    exact: bool,
+    key: &KeyExtractor,
+    progress: &crate::progress::ProgressTracker,
+) -> Result<HashSet<u64>> {
+    let file = File::open(file_path)
+        .with_context(|| format!("Failed to open file: {}", file_path.display()))?;
+    let chunks = ReverseChunks::new(file)?;
+
+    let mut seen = SeenSet::new(exact);
+    let mut keepers = HashSet::<u64>::new();
+    // `buf` holds the not-yet-resolved bytes, i.e. the tail of a line whose
+    // start still lies further left in the file.
+    let mut buf: Vec<u8> = Vec::new();
+
+    for block in chunks {
+        let (start, bytes) = block?;
+        // Prepend this (further-left) block to the unresolved bytes.
+        let mut combined = bytes;
+        combined.extend_from_slice(&buf);
+        buf = combined;
+        let region_start = start;
+
+        // The bytes up to and including the first newline belong to a line
+        // whose start lies further left; retain them for the next block.
+        if let Some(f) = buf.iter().position(|&b| b == b'\n') {
+            let emit = &buf[f + 1..];
+            emit_reverse_lines(
+                region_start + f as u64 + 1,
+                emit,
+                hasher,
+                key,
+                &mut seen,
+                &mut keepers,
+                progress,
+            )?;
+            buf = buf[..=f].to_vec();
+        }
+        // Otherwise there is no boundary yet; keep accumulating leftward.
+    }
+
+    // Whatever remains starts at offset 0: it is the file's first line.
+    if !buf.is_empty() {
+        emit_reverse_lines(0, &buf, hasher, key, &mut seen, &mut keepers, progress)?;
+    }
+
+    Ok(keepers)
+}
+
+/// Split `bytes` (covering `[region_start, region_start + bytes.len())`) into
+/// newline-terminated lines and mark each as a keeper, right-to-left.
+#[allow(clippy::too_many_arguments)]
+fn emit_reverse_lines(
+    region_start: u64,
+    bytes: &[u8],
+    hasher: &dyn LineHasher,
+    key: &KeyExtractor,
+    seen: &mut SeenSet,
+    keepers: &mut HashSet<u64>,
+    progress: &crate::progress::ProgressTracker,
+) -> Result<()> {
+    // Collect (start, slice) line spans, then visit them last-to-first so the
+    // last occurrence of each key wins.
+    let mut spans: Vec<(u64, &[u8])> = Vec::new();
+    let mut line_start = 0usize;
+    for (i, &b) in bytes.iter().enumerate() {
+        if b == b'\n' {
+            spans.push((region_start + line_start as u64, &bytes[line_start..=i]));
+            line_start = i + 1;
+        }
+    }
+    if line_start < bytes.len() {
+        // Trailing line with no terminating newline (file's final line).
+        spans.push((region_start + line_start as u64, &bytes[line_start..]));
+    }
+    for (start, slice) in spans.into_iter().rev() {
+        mark_keeper(start, slice, hasher, key, seen, keepers, progress)?;
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// Write `content` to a uniquely-named temp file and return its path.
+    fn write_temp(content: &[u8], tag: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "rsort_test_{}_{}",
+            tag,
+            std::process::id()
+        ));
+        let mut file = std::fs::File::create(&path).unwrap();
+        file.write_all(content).unwrap();
+        path
+    }
+
+    #[test]
+    fn last_occurrence_across_block_boundary() {
+        // A duplicate key appears at the very start and again near the end,
+        // with enough filler in between to span several reverse blocks.
+        let mut content = String::from("dup\n");
+        let mut idx = 0usize;
+        while content.len() < (REVERSE_BLOCK_SIZE as usize) + 8192 {
+            content.push_str(&format!("filler-{}\n", idx));
+            idx += 1;
+        }
+        let second_dup = content.len() as u64;
+        content.push_str("dup\n");
+        let last_line = content.len() as u64;
+        content.push_str("unique-last\n");
+
+        let path = write_temp(content.as_bytes(), "reverse");
+        let hasher = HashAlgorithm::Siphash.hasher();
+        let key = KeyExtractor::whole_line();
+        let progress = crate::progress::ProgressTracker::new(false);
+        let keepers =
+            find_last_occurrence_offsets(&path, hasher.as_ref(), false, &key, &progress).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        // The last occurrence of "dup" wins, not the first.
+        assert!(!keepers.contains(&0));
+        assert!(keepers.contains(&second_dup));
+        assert!(keepers.contains(&last_line));
+    }
+
+    #[test]
+    fn key_whole_line_is_verbatim() {
+        let key = KeyExtractor::whole_line();
+        assert_eq!(key.extract("a\tb\tc\n").unwrap(), "a\tb\tc\n");
+    }
+
+    #[test]
+    fn key_field_selects_column_without_newline() {
+        let key = KeyExtractor::field(2, '\t', MissingField::Error);
+        assert_eq!(key.extract("a\tb\tc\n").unwrap(), "b");
+        // Last field: trailing newline is stripped before splitting.
+        let last = KeyExtractor::field(3, '\t', MissingField::Error);
+        assert_eq!(last.extract("a\tb\tc\n").unwrap(), "c");
+    }
+
+    #[test]
+    fn key_range_selects_inclusive_bytes() {
+        let key = KeyExtractor::range(2, 4, MissingField::Error);
+        assert_eq!(key.extract("abcdef\n").unwrap(), "bcd");
+        // End past the line length clamps to the available bytes.
+        let clamped = KeyExtractor::range(4, 99, MissingField::Error);
+        assert_eq!(clamped.extract("abcdef\n").unwrap(), "def");
+    }
+
+    #[test]
+    fn missing_field_policy() {
+        let empty = KeyExtractor::field(5, '\t', MissingField::Empty);
+        assert_eq!(empty.extract("a\tb\n").unwrap(), "");
+
+        let error = KeyExtractor::field(5, '\t', MissingField::Error);
+        assert!(error.extract("a\tb\n").is_err());
+
+        let range_empty = KeyExtractor::range(10, 12, MissingField::Empty);
+        assert_eq!(range_empty.extract("abc\n").unwrap(), "");
+        let range_error = KeyExtractor::range(10, 12, MissingField::Error);
+        assert!(range_error.extract("abc\n").is_err());
+    }
+}
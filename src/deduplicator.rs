@@ -1,39 +1,116 @@
 use anyhow::{Context, Result};
-use hashbrown::HashSet;
+use rayon::prelude::*;
 use std::fs::File;
-use std::io::{BufWriter, Write};
+use std::io::{BufWriter, Read, Write};
 use std::path::Path;
 
-use crate::chunk_processor::{find_chunk_boundaries, process_chunk_stream};
-use crate::progress::ProgressTracker;
+use crate::chunk_processor::{
+    canonicalize, find_chunk_boundaries, find_last_occurrence_offsets, process_chunk_local,
+    spill_path_for, ChunkResult, HashAlgorithm, KeepMode, KeyExtractor, SeenSet,
+};
+use crate::progress::{ProgressTracker, ReportStats};
 
 pub struct Deduplicator {
     input_path: std::path::PathBuf,
     output_path: std::path::PathBuf,
     chunk_size_bytes: usize,
+    num_threads: usize,
+    hash_algorithm: HashAlgorithm,
+    exact: bool,
+    keep: KeepMode,
+    estimate: bool,
+    estimate_only: bool,
+    report: bool,
+    quarantine: Option<std::path::PathBuf>,
+    cdc: Option<crate::cdc::CdcParams>,
+    key: KeyExtractor,
 }
 
 impl Deduplicator {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         input_path: &Path,
         output_path: &Path,
         chunk_size_bytes: usize,
+        num_threads: usize,
+        hash_algorithm: HashAlgorithm,
+        exact: bool,
+        keep: KeepMode,
+        estimate: bool,
+        estimate_only: bool,
+        report: bool,
+        quarantine: Option<&Path>,
+        cdc: Option<crate::cdc::CdcParams>,
+        key: KeyExtractor,
     ) -> Result<Self> {
         Ok(Self {
             input_path: input_path.to_path_buf(),
             output_path: output_path.to_path_buf(),
             chunk_size_bytes,
+            num_threads,
+            hash_algorithm,
+            exact,
+            keep,
+            estimate,
+            estimate_only,
+            report,
+            quarantine: quarantine.map(|p| p.to_path_buf()),
+            cdc,
+            key,
         })
     }
 
     pub fn process(&mut self) -> Result<()> {
         let progress = ProgressTracker::new(true);
-        
+
+        // Content-defined chunking is a distinct binary dedup path that ignores
+        // line structure entirely.
+        if let Some(params) = self.cdc {
+            eprintln!("Deduplicating with content-defined chunking...");
+            return crate::cdc::deduplicate_cdc(
+                &self.input_path,
+                &self.output_path,
+                params,
+                &progress,
+            );
+        }
+
+        // Report mode is a pure scan: gather statistics and print them without
+        // producing any deduped output.
+        if self.report {
+            return self.process_report(&progress);
+        }
+
+        // Optional cardinality pre-pass: report the duplicate ratio up front
+        // and pre-size the dedup set to avoid repeated rehashing.
+        let mut capacity: Option<usize> = None;
+        if self.estimate || self.estimate_only {
+            eprintln!("Estimating cardinality...");
+            let est = crate::estimate::estimate_file(&self.input_path, &self.key)
+                .context("Failed to estimate file cardinality")?;
+            eprintln!("{}", est);
+            progress.set_expected_memory(est.expected_memory_bytes());
+            if self.estimate_only {
+                return Ok(());
+            }
+            capacity = Some(est.estimated_unique as usize);
+        }
+
+        // Keeping the last occurrence requires visiting the file in reverse,
+        // so it takes a dedicated two-pass path rather than the parallel merge.
+        if self.keep == KeepMode::Last {
+            return self.process_keep_last(&progress);
+        }
+
         eprintln!("Splitting file into chunks...");
         let chunks = find_chunk_boundaries(&self.input_path, self.chunk_size_bytes)
             .context("Failed to split file into chunks")?;
         
-        eprintln!("Found {} chunks, processing sequentially with streaming...", chunks.len());
+        eprintln!(
+            "Found {} chunks, processing with {} thread(s)...",
+            chunks.len(),
+            self.num_threads
+        );
         
         // Validate chunks before processing
         let file_size = crate::chunk_processor::get_file_size(&self.input_path)
@@ -59,48 +136,287 @@ impl Deduplicator {
             }
         }
         
+        // Process each chunk concurrently: every worker reads its own byte
+        // range and dedupes locally, spilling its surviving line bytes to a
+        // temp file and returning only `(hash, len)` metadata.
+        //
+        // Memory note: the line text is kept on disk in the per-chunk spill
+        // files, so during the merge memory holds every chunk's `(hash, len)`
+        // entries (one per locally-unique line) and the global key set (one
+        // `u128` per distinct line, plus its canonical key in --exact mode)
+        // rather than the full unique-line text.
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.num_threads)
+            .build()
+            .context("Failed to build thread pool")?;
+
+        let hasher = self.hash_algorithm.hasher();
+        let results: Vec<ChunkResult> = pool.install(|| {
+            chunks
+                .par_iter()
+                .enumerate()
+                .map(|(idx, chunk)| {
+                    process_chunk_local(
+                        &self.input_path,
+                        chunk,
+                        hasher.as_ref(),
+                        self.exact,
+                        self.quarantine.is_some(),
+                        &self.key,
+                        &spill_path_for(&self.output_path, idx),
+                        &progress,
+                    )
+                    .with_context(|| {
+                        format!(
+                            "Failed to process chunk {} (offset {}-{}, file: {})",
+                            idx,
+                            chunk.start_offset,
+                            chunk.end_offset,
+                            self.input_path.display()
+                        )
+                    })
+                })
+                .collect::<Result<Vec<_>>>()
+        })?;
+
         // Open output file with larger buffer for better I/O performance (1MB buffer)
         let output_file = File::create(&self.output_path)
             .with_context(|| format!("Failed to create output file: {}", self.output_path.display()))?;
         let mut writer = BufWriter::with_capacity(1024 * 1024, output_file); // 1MB buffer for better I/O
-        let mut global_seen = HashSet::<u64>::new();
-        
-        // Process chunks sequentially, streaming directly to output
-        let total_chunks = chunks.len();
-        for (idx, chunk) in chunks.iter().enumerate() {
-            if (idx + 1) % 10 == 0 || idx == 0 {
-                eprintln!("Processing chunk {}/{} (offset {}-{})...", 
-                         idx + 1, total_chunks, chunk.start_offset, chunk.end_offset);
+        let mut global_seen = match capacity {
+            Some(cap) => SeenSet::with_capacity(self.exact, cap),
+            None => SeenSet::new(self.exact),
+        };
+
+        // Divert any lines that failed UTF-8 decoding to the quarantine file
+        // (in original order) so they are accounted for rather than lost.
+        if let Some(ref quarantine_path) = self.quarantine {
+            let has_quarantine = results.iter().any(|r| !r.quarantined.is_empty());
+            if has_quarantine {
+                let file = File::create(quarantine_path).with_context(|| {
+                    format!("Failed to create quarantine file: {}", quarantine_path.display())
+                })?;
+                let mut qwriter = BufWriter::new(file);
+                for result in &results {
+                    for (offset, bytes) in &result.quarantined {
+                        write_quarantine_entry(&mut qwriter, *offset, bytes)
+                            .context("Failed to write quarantine entry")?;
+                    }
+                }
+                qwriter.flush().context("Failed to flush quarantine file")?;
             }
-            
-            process_chunk_stream(
-                &self.input_path,
-                chunk,
-                &mut writer,
-                &mut global_seen,
-                &progress,
-            )
-            .with_context(|| format!(
-                "Failed to process chunk {} (offset {}-{}, file: {})",
-                idx,
-                chunk.start_offset,
-                chunk.end_offset,
-                self.input_path.display()
-            ))?;
-            
-            // Flush periodically to ensure progress is written
-            if (idx + 1) % 50 == 0 {
-                writer.flush().context("Failed to flush output buffer")?;
+        }
+
+        // Merge pass: walk chunks in original order so the first global
+        // occurrence of each line is the one that survives and is written. Each
+        // chunk's line text is streamed back from its spill file rather than
+        // held in memory.
+        let mut line_buf: Vec<u8> = Vec::new();
+        for result in &results {
+            progress.increment_duplicates(result.duplicates);
+            let Some(ref spill) = result.spill else {
+                continue;
+            };
+            let mut reader = spill.open()?;
+            for (hash, len) in &result.lines {
+                line_buf.clear();
+                line_buf.resize(*len as usize, 0);
+                reader.read_exact(&mut line_buf)
+                    .context("Failed to read line from spill file")?;
+
+                // The canonical is only consulted in --exact mode; skip the key
+                // extraction and lowercasing on the default (Fast) hot path.
+                let canonical = if self.exact {
+                    let line = std::str::from_utf8(&line_buf)
+                        .context("Spilled line was not valid UTF-8")?;
+                    canonicalize(&self.key.extract(line)?)
+                } else {
+                    String::new()
+                };
+                if global_seen.insert(*hash, &canonical) {
+                    writer.write_all(&line_buf)
+                        .context("Failed to write line to output")?;
+                } else {
+                    progress.increment_duplicates(1);
+                }
+
+                // Periodic flush to reduce bottlenecks
+                if global_seen.len() % 100_000 == 0 {
+                    writer.flush().context("Failed to flush output buffer")?;
+                }
             }
         }
-        
+
         // Final flush
         writer.flush().context("Failed to flush final output")?;
         
         let metrics = progress.finish();
         eprintln!("{}", metrics);
-        
+
+        Ok(())
+    }
+
+    /// Two-pass dedup that retains the last occurrence of each line.
+    ///
+    /// The first pass scans the file backward to record the start offset of
+    /// each key's final occurrence; the second pass reads forward and writes
+    /// only those lines, so the output stays in original order.
+    fn process_keep_last(&mut self, progress: &ProgressTracker) -> Result<()> {
+        use std::io::{BufRead, BufReader};
+
+        eprintln!("Scanning file in reverse to locate last occurrences...");
+        let hasher = self.hash_algorithm.hasher();
+        let keepers = find_last_occurrence_offsets(
+            &self.input_path,
+            hasher.as_ref(),
+            self.exact,
+            &self.key,
+            progress,
+        )
+        .context("Failed to scan file for last occurrences")?;
+
+        let output_file = File::create(&self.output_path)
+            .with_context(|| format!("Failed to create output file: {}", self.output_path.display()))?;
+        let mut writer = BufWriter::with_capacity(1024 * 1024, output_file);
+
+        let input = File::open(&self.input_path)
+            .with_context(|| format!("Failed to open file: {}", self.input_path.display()))?;
+        let mut reader = BufReader::with_capacity(256 * 1024, input);
+
+        let mut quarantine_writer = match &self.quarantine {
+            Some(path) => Some(BufWriter::new(File::create(path).with_context(|| {
+                format!("Failed to create quarantine file: {}", path.display())
+            })?)),
+            None => None,
+        };
+
+        let mut offset = 0u64;
+        let mut buf = Vec::new();
+        let mut written = 0u64;
+        loop {
+            buf.clear();
+            let bytes_read = reader.read_until(b'\n', &mut buf)
+                .with_context(|| format!("Failed to read line at offset {}", offset))?;
+            if bytes_read == 0 {
+                break;
+            }
+            let start = offset;
+            offset += bytes_read as u64;
+
+            // Mirror the reverse pass: skip bare newlines and quarantine or
+            // drop non-UTF-8 lines.
+            if buf == b"\n" {
+                continue;
+            }
+            if std::str::from_utf8(&buf).is_err() {
+                if let Some(ref mut w) = quarantine_writer {
+                    write_quarantine_entry(w, start, &buf)
+                        .context("Failed to write quarantine entry")?;
+                }
+                continue;
+            }
+
+            if keepers.contains(&start) {
+                writer.write_all(&buf).context("Failed to write line to output")?;
+                written += 1;
+                if written % 100_000 == 0 {
+                    writer.flush().context("Failed to flush output buffer")?;
+                }
+            } else {
+                progress.increment_duplicates(1);
+            }
+        }
+
+        writer.flush().context("Failed to flush final output")?;
+        if let Some(mut w) = quarantine_writer {
+            w.flush().context("Failed to flush quarantine file")?;
+        }
+
+        let metrics = progress.finish();
+        eprintln!("{}", metrics);
+
         Ok(())
     }
+
+    /// Scan the whole file and report statistics without writing output.
+    fn process_report(&mut self, progress: &ProgressTracker) -> Result<()> {
+        use std::io::{BufRead, BufReader};
+
+        eprintln!("Scanning file...");
+        let hasher = self.hash_algorithm.hasher();
+        let file = File::open(&self.input_path)
+            .with_context(|| format!("Failed to open file: {}", self.input_path.display()))?;
+        let mut reader = BufReader::with_capacity(256 * 1024, file);
+        let mut seen = SeenSet::new(self.exact);
+
+        let mut total_lines = 0u64;
+        let mut unique_lines = 0u64;
+        let mut duplicate_lines = 0u64;
+        let mut invalid_lines = 0u64;
+        let mut invalid_offsets = Vec::new();
+        let mut longest_line = 0usize;
+
+        let mut offset = 0u64;
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            let bytes_read = reader.read_until(b'\n', &mut buf)
+                .with_context(|| format!("Failed to read line at offset {}", offset))?;
+            if bytes_read == 0 {
+                break;
+            }
+            let start = offset;
+            offset += bytes_read as u64;
+
+            if buf == b"\n" {
+                continue;
+            }
+
+            match std::str::from_utf8(&buf) {
+                Ok(line) => {
+                    total_lines += 1;
+                    let len = line.trim_end_matches('\n').len();
+                    if len > longest_line {
+                        longest_line = len;
+                    }
+                    let canonical = canonicalize(&self.key.extract(line)?);
+                    let hash = hasher.hash128(&canonical);
+                    if seen.insert(hash, &canonical) {
+                        unique_lines += 1;
+                    } else {
+                        duplicate_lines += 1;
+                    }
+                }
+                Err(_) => {
+                    invalid_lines += 1;
+                    invalid_offsets.push(start);
+                }
+            }
+        }
+
+        let mut metrics = progress.finish();
+        metrics.lines_processed = total_lines;
+        metrics.duplicates_removed = duplicate_lines;
+        metrics.report = Some(ReportStats {
+            unique_lines,
+            invalid_lines,
+            invalid_offsets,
+            longest_line,
+        });
+        eprintln!("{}", metrics);
+
+        Ok(())
+    }
+}
+
+/// Write a single quarantined line to the side file: an offset header followed
+/// by the original bytes verbatim.
+fn write_quarantine_entry<W: Write>(writer: &mut W, offset: u64, bytes: &[u8]) -> Result<()> {
+    writeln!(writer, "# offset {}", offset)?;
+    writer.write_all(bytes)?;
+    if !bytes.ends_with(b"\n") {
+        writer.write_all(b"\n")?;
+    }
+    Ok(())
 }
 
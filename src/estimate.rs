@@ -0,0 +1,188 @@
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+use crate::chunk_processor::{canonicalize, KeyExtractor};
+
+/// Precision of the HyperLogLog sketch: `2^P` registers.
+const P: u32 = 14;
+
+/// Result of the cardinality pre-pass.
+///
+/// Produced by a single HyperLogLog scan so the caller can pre-size the dedup
+/// hash set and report the expected memory footprint before the real run.
+pub struct Estimate {
+    pub total_lines: u64,
+    pub estimated_unique: u64,
+    pub relative_error: f64,
+}
+
+impl Estimate {
+    /// Estimated fraction of lines that are duplicates, in `[0.0, 1.0]`.
+    pub fn duplicate_ratio(&self) -> f64 {
+        if self.total_lines == 0 {
+            0.0
+        } else {
+            1.0 - (self.estimated_unique as f64 / self.total_lines as f64).min(1.0)
+        }
+    }
+
+    /// Absolute ± margin implied by the sketch's standard relative error.
+    pub fn error_margin(&self) -> u64 {
+        (self.estimated_unique as f64 * self.relative_error).round() as u64
+    }
+
+    /// Rough expected memory for the dedup hash set, in bytes.
+    ///
+    /// Each entry is a 128-bit key plus hashbrown's per-slot overhead; 24
+    /// bytes is a conservative per-entry approximation.
+    pub fn expected_memory_bytes(&self) -> u64 {
+        self.estimated_unique.saturating_mul(24)
+    }
+}
+
+impl std::fmt::Display for Estimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let margin = self.error_margin();
+        writeln!(f, "Cardinality estimate (HyperLogLog, p={}):", P)?;
+        writeln!(f, "  Total lines: {}", self.total_lines)?;
+        writeln!(
+            f,
+            "  Estimated unique: {} (±{}, ~{:.2}% error)",
+            self.estimated_unique,
+            margin,
+            self.relative_error * 100.0
+        )?;
+        writeln!(
+            f,
+            "  Estimated duplicates: {:.2}%",
+            self.duplicate_ratio() * 100.0
+        )?;
+        write!(
+            f,
+            "  Expected hash-set memory: ~{:.1} MB",
+            self.expected_memory_bytes() as f64 / (1024.0 * 1024.0)
+        )
+    }
+}
+
+/// Run a HyperLogLog pre-pass over the file and estimate its unique-key count.
+///
+/// This is a full pass over every line, not a sample: the sketch is cheap
+/// enough that a partial scan would trade accuracy for little real saving.
+/// Each line is reduced to its dedup key with `key` (so the estimate matches
+/// what the real run will actually deduplicate on), then a 64-bit hash of the
+/// canonical key is split into a `P`-bit register index and a rank (leading
+/// zeros + 1 of the remaining bits); the per-register maxima feed the
+/// harmonic-mean estimator `E = α_m · m² / Σ 2^(-reg)` with the standard
+/// small-range correction.
+pub fn estimate_file(file_path: &std::path::Path, key: &KeyExtractor) -> Result<Estimate> {
+    let file = File::open(file_path)
+        .with_context(|| format!("Failed to open file: {}", file_path.display()))?;
+    let mut reader = BufReader::with_capacity(256 * 1024, file);
+
+    let m = 1usize << P;
+    let mut registers = vec![0u8; m];
+    let mut total_lines = 0u64;
+
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        let bytes_read = reader
+            .read_until(b'\n', &mut buf)
+            .context("Failed to read line during estimate scan")?;
+        if bytes_read == 0 {
+            break;
+        }
+        // Match the dedup reader: skip bare newlines and non-UTF-8 lines.
+        if buf == b"\n" {
+            continue;
+        }
+        let Ok(line) = std::str::from_utf8(&buf) else {
+            continue;
+        };
+        total_lines += 1;
+
+        // Skip lines whose key is missing under the error policy; they abort the
+        // real run, but the estimate is advisory and should not be derailed.
+        let Ok(key) = key.extract(line) else {
+            continue;
+        };
+        let hash = xxhash_rust::xxh3::xxh3_64(canonicalize(&key).as_bytes());
+        let index = (hash >> (64 - P)) as usize;
+        let rank = ((hash << P).leading_zeros().min(64 - P)) as u8 + 1;
+        if rank > registers[index] {
+            registers[index] = rank;
+        }
+    }
+
+    let estimated_unique = estimate_cardinality(&registers);
+    let relative_error = 1.04 / (m as f64).sqrt();
+
+    Ok(Estimate {
+        total_lines,
+        estimated_unique,
+        relative_error,
+    })
+}
+
+/// Harmonic-mean estimator with the small-range (linear counting) correction;
+/// no large-range correction is applied.
+fn estimate_cardinality(registers: &[u8]) -> u64 {
+    let m = registers.len() as f64;
+    let alpha = 0.7213 / (1.0 + 1.079 / m);
+    let sum: f64 = registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+    let mut estimate = alpha * m * m / sum;
+
+    let zeros = registers.iter().filter(|&&r| r == 0).count();
+    if estimate <= 2.5 * m && zeros > 0 {
+        // Small-range correction: linear counting.
+        estimate = m * (m / zeros as f64).ln();
+    }
+
+    // The original HLL paper's large-range correction assumes a 32-bit hash
+    // space; we hash with xxh3_64, so that correction would fire spuriously
+    // well below saturation and is omitted.
+
+    estimate.round() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_registers_estimate_zero() {
+        // All-zero registers: linear counting yields 0 unique.
+        let registers = vec![0u8; 1 << P];
+        assert_eq!(estimate_cardinality(&registers), 0);
+    }
+
+    #[test]
+    fn estimate_is_close_for_known_cardinality() {
+        // Build a sketch from a known set of distinct keys and check the
+        // estimate lands within the sketch's expected error envelope.
+        let m = 1usize << P;
+        let mut registers = vec![0u8; m];
+        let n = 50_000u64;
+        for i in 0..n {
+            let hash = xxhash_rust::xxh3::xxh3_64(&i.to_le_bytes());
+            let index = (hash >> (64 - P)) as usize;
+            let rank = ((hash << P).leading_zeros().min(64 - P)) as u8 + 1;
+            if rank > registers[index] {
+                registers[index] = rank;
+            }
+        }
+        let estimate = estimate_cardinality(&registers) as f64;
+        let relative_error = 1.04 / (m as f64).sqrt();
+        // Allow a generous 4σ band to keep the test deterministic-ish.
+        let tolerance = n as f64 * relative_error * 4.0;
+        assert!(
+            (estimate - n as f64).abs() < tolerance,
+            "estimate {} too far from {} (tolerance {})",
+            estimate,
+            n,
+            tolerance
+        );
+    }
+}
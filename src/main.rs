@@ -4,8 +4,11 @@ use std::path::PathBuf;
 
 mod deduplicator;
 mod chunk_processor;
+mod cdc;
+mod estimate;
 mod progress;
 
+use chunk_processor::{HashAlgorithm, KeepMode, KeyExtractor, MissingField};
 use deduplicator::Deduplicator;
 
 #[derive(Parser, Debug)]
@@ -25,6 +28,77 @@ struct Args {
     /// Number of parallel threads (default: CPU count)
     #[arg(long)]
     threads: Option<usize>,
+
+    /// Hash algorithm used for the 128-bit dedup key
+    #[arg(long, value_enum, default_value_t = HashAlgorithm::Siphash)]
+    hash_algorithm: HashAlgorithm,
+
+    /// Confirm every hash hit with a byte comparison (higher memory, zero collision risk)
+    #[arg(long)]
+    exact: bool,
+
+    /// Which occurrence of a duplicated line to keep
+    #[arg(long, value_enum, default_value_t = KeepMode::First)]
+    keep: KeepMode,
+
+    /// Run a cardinality pre-pass to report the duplicate ratio and pre-size the hash set
+    #[arg(long)]
+    estimate: bool,
+
+    /// Run the cardinality pre-pass and exit without writing output
+    #[arg(long)]
+    estimate_only: bool,
+
+    /// Scan the file and report statistics without writing deduped output
+    #[arg(long)]
+    report: bool,
+
+    /// Write lines that fail UTF-8 decoding to this side file instead of dropping them
+    #[arg(long)]
+    quarantine: Option<PathBuf>,
+
+    /// Deduplicate arbitrary binary data with content-defined (FastCDC) chunking
+    #[arg(long)]
+    cdc: bool,
+
+    /// Target average chunk size in bytes for --cdc
+    #[arg(long, default_value = "16384")]
+    avg_size: usize,
+
+    /// Minimum chunk size in bytes for --cdc
+    #[arg(long, default_value = "4096")]
+    min_size: usize,
+
+    /// Maximum chunk size in bytes for --cdc
+    #[arg(long, default_value = "65536")]
+    max_size: usize,
+
+    /// Dedup on the Nth (1-based) delimited field instead of the whole line
+    #[arg(long)]
+    key_field: Option<usize>,
+
+    /// Field delimiter for --key-field
+    #[arg(long, default_value = "\t")]
+    key_delimiter: char,
+
+    /// Dedup on a 1-based inclusive byte range "a-b" of each line
+    #[arg(long)]
+    key_range: Option<String>,
+
+    /// Behavior when the requested key field/range is missing from a line
+    #[arg(long, value_enum, default_value_t = MissingField::Error)]
+    missing_field: MissingField,
+}
+
+/// Parse a 1-based inclusive "a-b" byte range, returning `None` when malformed.
+fn parse_range(spec: &str) -> Option<(usize, usize)> {
+    let (a, b) = spec.split_once('-')?;
+    let start: usize = a.trim().parse().ok()?;
+    let end: usize = b.trim().parse().ok()?;
+    if start == 0 || start > end {
+        return None;
+    }
+    Some((start, end))
 }
 
 fn main() -> Result<()> {
@@ -56,35 +130,42 @@ fn main() -> Result<()> {
         }
     }
     
-    // Check if output directory exists, create if not
-    if let Some(parent) = args.output.parent() {
-        if let Err(e) = std::fs::create_dir_all(parent) {
-            if e.kind() == std::io::ErrorKind::PermissionDenied {
-                eprintln!("Error: Permission denied creating output directory: {}", parent.display());
-            } else {
-                eprintln!("Error: Failed to create output directory: {}", parent.display());
-                eprintln!("Details: {}", e);
+    // --report and --estimate-only are pure scans that never write output, so
+    // skip the output pre-flight entirely for them: creating the file here would
+    // truncate an existing output the caller never asked us to touch.
+    let writes_output = !args.report && !args.estimate_only;
+
+    if writes_output {
+        // Check if output directory exists, create if not
+        if let Some(parent) = args.output.parent() {
+            if let Err(e) = std::fs::create_dir_all(parent) {
+                if e.kind() == std::io::ErrorKind::PermissionDenied {
+                    eprintln!("Error: Permission denied creating output directory: {}", parent.display());
+                } else {
+                    eprintln!("Error: Failed to create output directory: {}", parent.display());
+                    eprintln!("Details: {}", e);
+                }
+                std::process::exit(1);
             }
-            std::process::exit(1);
         }
-    }
-    
-    // Check if we can write to output location
-    match std::fs::File::create(&args.output) {
-        Ok(_) => {}
-        Err(e) => {
-            if e.kind() == std::io::ErrorKind::PermissionDenied {
-                eprintln!("Error: Permission denied writing to output file: {}", args.output.display());
-                eprintln!("Please check file permissions.");
-            } else {
-                eprintln!("Error: Failed to create output file: {}", args.output.display());
-                eprintln!("Details: {}", e);
+
+        // Check if we can write to output location
+        match std::fs::File::create(&args.output) {
+            Ok(_) => {}
+            Err(e) => {
+                if e.kind() == std::io::ErrorKind::PermissionDenied {
+                    eprintln!("Error: Permission denied writing to output file: {}", args.output.display());
+                    eprintln!("Please check file permissions.");
+                } else {
+                    eprintln!("Error: Failed to create output file: {}", args.output.display());
+                    eprintln!("Details: {}", e);
+                }
+                std::process::exit(1);
             }
-            std::process::exit(1);
         }
     }
-    
-    let num_threads = args.threads.unwrap_or_else(|| num_cpus::get());
+
+    let num_threads = args.threads.unwrap_or_else(num_cpus::get);
     
     if num_threads == 0 {
         eprintln!("Error: Number of threads must be greater than 0");
@@ -95,11 +176,61 @@ fn main() -> Result<()> {
         eprintln!("Error: Chunk size must be greater than 0");
         std::process::exit(1);
     }
-    
+
+    let cdc = if args.cdc {
+        if !(args.min_size <= args.avg_size && args.avg_size <= args.max_size) {
+            eprintln!("Error: --cdc sizes must satisfy min-size <= avg-size <= max-size");
+            std::process::exit(1);
+        }
+        if args.min_size == 0 {
+            eprintln!("Error: --cdc min-size must be greater than 0");
+            std::process::exit(1);
+        }
+        Some(cdc::CdcParams {
+            min_size: args.min_size,
+            avg_size: args.avg_size,
+            max_size: args.max_size,
+        })
+    } else {
+        None
+    };
+
+    let key = match (args.key_field, args.key_range.as_deref()) {
+        (Some(_), Some(_)) => {
+            eprintln!("Error: --key-field and --key-range are mutually exclusive");
+            std::process::exit(1);
+        }
+        (Some(field), None) => {
+            if field == 0 {
+                eprintln!("Error: --key-field is 1-based and must be greater than 0");
+                std::process::exit(1);
+            }
+            KeyExtractor::field(field, args.key_delimiter, args.missing_field)
+        }
+        (None, Some(range)) => match parse_range(range) {
+            Some((start, end)) => KeyExtractor::range(start, end, args.missing_field),
+            None => {
+                eprintln!("Error: --key-range must be of the form \"a-b\" with 1 <= a <= b");
+                std::process::exit(1);
+            }
+        },
+        (None, None) => KeyExtractor::whole_line(),
+    };
+
     let mut deduplicator = Deduplicator::new(
         &args.input,
         &args.output,
         args.chunk_size * 1024 * 1024, // Convert MB to bytes
+        num_threads,
+        args.hash_algorithm,
+        args.exact,
+        args.keep,
+        args.estimate,
+        args.estimate_only,
+        args.report,
+        args.quarantine.as_deref(),
+        cdc,
+        key,
     )?;
     
     if let Err(e) = deduplicator.process() {
@@ -6,6 +6,7 @@ use std::time::Instant;
 pub struct ProgressTracker {
     lines_processed: Arc<AtomicU64>,
     duplicates_removed: Arc<AtomicU64>,
+    expected_memory: Arc<AtomicU64>,
     start_time: Instant,
     progress_bar: Option<ProgressBar>,
 }
@@ -27,11 +28,18 @@ impl ProgressTracker {
         Self {
             lines_processed: Arc::new(AtomicU64::new(0)),
             duplicates_removed: Arc::new(AtomicU64::new(0)),
+            expected_memory: Arc::new(AtomicU64::new(0)),
             start_time: Instant::now(),
             progress_bar,
         }
     }
 
+    /// Record the expected hash-set memory (in bytes) from the estimate pre-pass,
+    /// so it is reported alongside the final metrics.
+    pub fn set_expected_memory(&self, bytes: u64) {
+        self.expected_memory.store(bytes, Ordering::Relaxed);
+    }
+
     pub fn increment_lines(&self, count: u64) {
         let prev = self.lines_processed.fetch_add(count, Ordering::Relaxed);
         let new_total = prev + count;
@@ -54,10 +62,17 @@ impl ProgressTracker {
             pb.finish_with_message("Completed");
         }
 
+        let expected_memory = match self.expected_memory.load(Ordering::Relaxed) {
+            0 => None,
+            bytes => Some(bytes),
+        };
+
         Metrics {
             lines_processed: self.lines_processed.load(Ordering::Relaxed),
             duplicates_removed: self.duplicates_removed.load(Ordering::Relaxed),
             processing_time: self.start_time.elapsed(),
+            expected_memory,
+            report: None,
         }
     }
 }
@@ -66,13 +81,46 @@ pub struct Metrics {
     pub lines_processed: u64,
     pub duplicates_removed: u64,
     pub processing_time: std::time::Duration,
+    pub expected_memory: Option<u64>,
+    pub report: Option<ReportStats>,
+}
+
+/// Extra statistics gathered by `--report` scan mode.
+pub struct ReportStats {
+    pub unique_lines: u64,
+    pub invalid_lines: u64,
+    pub invalid_offsets: Vec<u64>,
+    pub longest_line: usize,
 }
 
 impl std::fmt::Display for Metrics {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(ref report) = self.report {
+            writeln!(f, "Scan Report")?;
+            writeln!(f, "  Total lines: {}", self.lines_processed)?;
+            writeln!(f, "  Unique lines: {}", report.unique_lines)?;
+            writeln!(f, "  Duplicate lines: {}", self.duplicates_removed)?;
+            writeln!(f, "  Longest line: {} bytes", report.longest_line)?;
+            writeln!(f, "  Invalid (non-UTF-8) lines: {}", report.invalid_lines)?;
+            if !report.invalid_offsets.is_empty() {
+                let offsets: Vec<String> =
+                    report.invalid_offsets.iter().map(|o| o.to_string()).collect();
+                writeln!(f, "  Invalid line offsets: {}", offsets.join(", "))?;
+            }
+            writeln!(f, "  Scan time: {:.2}s", self.processing_time.as_secs_f64())?;
+            return Ok(());
+        }
+
         writeln!(f, "Deduplication Complete!")?;
         writeln!(f, "  Lines processed: {}", self.lines_processed)?;
         writeln!(f, "  Duplicates removed: {}", self.duplicates_removed)?;
+        if let Some(bytes) = self.expected_memory {
+            writeln!(
+                f,
+                "  Expected memory: ~{:.1} MB",
+                bytes as f64 / (1024.0 * 1024.0)
+            )?;
+        }
         writeln!(f, "  Processing time: {:.2}s", self.processing_time.as_secs_f64())?;
         if self.lines_processed > 0 {
             writeln!(